@@ -1,515 +1,922 @@
-/*
-Chip8 memory model looks like this: 
-
-+----------------+= 0xFFF (4095) End of Chip-8 RAM
-|                |
-|                |
-|                |
-|                |
-|                |
-| 0x200 to 0xFFF | 
-|     Chip-8     |
-| Program / Data |
-|     Space      |
-|                |
-|                |
-|                |
-|                |
-|                |
-|                |
-|                |
-+----------------+= 0x200 (512) Start of Chip-8 programs
-|                |
-| Reserved for   |
-|  interpreter   |
-+----------------+= 0x000 (0) Start of Chip-8 RAM
-
-Because Chip8 is a Virtual Machine, space reserved for interpreter is the place 
-where all the Chip8 internals should be (registers, stack, display memory and other stuff used by specific VM implementation)
-
-Internals required by specification:
-- 16 8-bit general purpose registers: V0...VF
-- 16-bit index register
-- 8-bit Delay Timer
-- 8-bit Sound Timer
-- 16-bit Program Counter
-- 8-bit Stack Pointer
-- Stack allowing 16-levels of nested subroutines
-- Display buffer for monochromatic 64px x 32px display
-- Built-in font sprites
-
-After calculating space required by above internals 
-we can calculate how much space we've left for our custom implementation specific Chip8 elements.
-
-256 bytes - Display buffer
- 80 bytes - Built-in font
- 32 bytes - Stack
- 16 bytes - General purpose registers
-  2 bytes - Index register
-  2 bytes - Program counter
-  1 byte  - Delay Timer
-  1 byte  - Sound Timer
-  1 byte  - Stack Pointer
-----------------------------------------
-391 bytes - Total
-
-512 - 391 = 121 bytes
- 
-This implementation will have following memory mapping of the internals:
-0x000 - 0x04F : Built-in font
-0x050 - 0x05F : V registers
-0x060 - 0x07F : Stack
-0x080 - 0x17F : Display buffer
-0x180         : Stack pointer
-0x181         : Sound timer
-0x182         : Delay timer
-0x183 - 0x184 : Program counter
-0x185 - 0x186 : Index register
-0x187         : [CUSTOM] Wait key press flag (If most significant bit is indicating if VM is waiting for key, 4 least significant bits tells to which register save the key)
-0x188 - 0x198 : [CUSTOM] Key presses flags
-*/
-
-macro_rules! decode_instruction {
-    ($mnemonic:literal, $condition:expr, $op:expr) => {
-        if $condition {
-            $op;
-        }
-    }
-}
-
-const FONT: [u32; 16] = [
-    0xF999F, 0x26227, 0xF1F8F, 0xF1F1F,
-    0x99F11, 0xF8F1F, 0xF8F9F, 0xF1244,
-    0xF9F9F, 0xF9F1F, 0xF9F99, 0xE9E9E,
-    0xF888F, 0xE999E, 0xF8F8F, 0xF8F88
-];
-
-const FONT_ADDR: u16                = 0x0000;
-const V_REGISTERS_ADDR: u16         = 0x0050;
-const STACK_ADDR: u16               = 0x0060;
-const DISPLAY_BUFFER_ADDR: u16      = 0x0080;
-const STACK_POINTER_ADDR: u16       = 0x0180;
-const SOUND_TIMER_ADDR: u16         = 0x0181;
-const DELAY_TIMER_ADDR: u16         = 0x0182;
-const PROGRAM_COUNTER_ADDR: u16     = 0x0183;
-const INDEX_REGISTER_ADDR: u16      = 0x0185;
-const WAIT_KEY_PRESS_FLAG_ADDR: u16 = 0x0187;
-const KEY_FLAGS_ADDR: u16           = 0x0188;
-
-const DISPLAY_WIDTH: usize = 64;
-const DISPLAY_HEIGHT: usize = 32;
-const DISPLAY_BUFFER_SIZE: usize = DISPLAY_HEIGHT * DISPLAY_WIDTH / 8;
-
-pub struct Chip8 {
-    memory: [u8; 0x1000]
-}
-
-impl Chip8 {
-    pub fn new() -> Self {
-        let mut chip8 = Self{ memory: [0; 0x1000] };
-
-        // Install font
-        for digit in 0..16 {
-            for byte in 0..5 {
-                chip8.memory[digit * byte] = (FONT[digit] >> (16 - byte * 4) & 0xFF) as u8;
-            }
-        }
-
-        return chip8;
-    }
-
-    pub fn get_display_buffer(&self) -> [u8; DISPLAY_BUFFER_SIZE] {
-        let start = usize::from(DISPLAY_BUFFER_ADDR);
-        let end: usize = usize::from(DISPLAY_BUFFER_ADDR) + usize::from(DISPLAY_BUFFER_SIZE);
-        return self.memory[start..end]
-            .try_into()
-            .expect("Slice containing display buffer has incorrect length");
-    }
-
-    pub fn execute_instruction(&mut self) {
-        if self.is_waiting_for_key() {
-            return;
-        }
-        
-        let opcode = self.fetch_opcode();
-
-        let c = opcode >> 12 & 0xF;
-        let nnn: u16 = opcode & 0xFFF;
-        let nn: u8 = (opcode & 0xFF) as u8;
-        let n: u8 = (opcode & 0xF) as u8;
-        let x: u8 = (opcode >> 8 & 0xF) as u8;
-        let y: u8 = (opcode >> 4 & 0xF) as u8;
-
-        decode_instruction!("CLS"          , c == 0x0 && nn == 0xE0, self.cls());
-        decode_instruction!("RET"          , c == 0x0 && nn == 0xEE, self.ret());
-        decode_instruction!("JMP nnn"      , c == 0x1              , self.jmp_direct(nnn));
-        decode_instruction!("CALL nnn"     , c == 0x2              , self.call(nnn));
-        decode_instruction!("SE Vx, nn"    , c == 0x3              , self.se_immedate(x, nn));
-        decode_instruction!("SNE Vx, nn"   , c == 0x4              , self.sne_immedate(x, nn));
-        decode_instruction!("SE Vx, Vy"    , c == 0x5 && n == 0x0  , self.se_registers(x, y));
-        decode_instruction!("LD Vx, nn"    , c == 0x6              , self.ld_immedate(x, nn));
-        decode_instruction!("ADD Vx, nn"   , c == 0x7              , self.add_immedate(x, nn));
-        decode_instruction!("LD Vx, Vy"    , c == 0x8 && n == 0x0  , self.ld_registers(x, y));
-        decode_instruction!("OR Vx, Vy"    , c == 0x8 && n == 0x1  , self.or_registers(x, y));
-        decode_instruction!("AND Vx, Vy"   , c == 0x8 && n == 0x2  , self.and_registers(x, y));
-        decode_instruction!("XOR Vx, Vy"   , c == 0x8 && n == 0x3  , self.xor_registers(x, y));
-        decode_instruction!("ADD Vx, Vy"   , c == 0x8 && n == 0x4  , self.add_registers(x, y));
-        decode_instruction!("SUB Vx, Vy"   , c == 0x8 && n == 0x5  , self.sub_registers(x, y));
-        decode_instruction!("SHR Vx"       , c == 0x8 && n == 0x6  , self.shr(x));
-        decode_instruction!("SUBN Vx, Vy"  , c == 0x8 && n == 0x7  , self.subn_registers(x, y));
-        decode_instruction!("SHL Vx"       , c == 0x8 && n == 0xE  , self.shl(x));
-        decode_instruction!("SNE Vx, Vy"   , c == 0x9 && n == 0x0  , self.sne_registers(x, y));
-        decode_instruction!("LD I, nnn"    , c == 0xA              , self.ld_index(nnn));
-        decode_instruction!("JMP V0, nnn"  , c == 0xB              , self.jmp_indirect(nnn));
-        decode_instruction!("RND Vx, nn"   , c == 0xC              , self.rnd(x, nn));
-        decode_instruction!("DRW Vx, Vy, n", c == 0xD              , self.drw(x, y, n));
-        decode_instruction!("SKP Vx"       , c == 0xE && nn == 0x9E, self.skp(x));
-        decode_instruction!("SKNP Vx"      , c == 0xE && nn == 0xA1, self.sknp(x));
-        decode_instruction!("LD Vx, DT"    , c == 0xF && nn == 0x07, self.ld_from_dt(x));
-        decode_instruction!("LD Vx, K"     , c == 0xF && nn == 0x0A, self.ld_key_press(x));
-        decode_instruction!("LD DT, Vx"    , c == 0xF && nn == 0x15, self.ld_into_dt(x));
-        decode_instruction!("LD ST, Vx"    , c == 0xF && nn == 0x18, self.ld_into_st(x));
-        decode_instruction!("ADD I, Vx"    , c == 0xF && nn == 0x1E, self.add_index(x));
-        decode_instruction!("LD F, Vx"     , c == 0xF && nn == 0x29, self.ld_font_addr(x));
-        decode_instruction!("LD B, Vx"     , c == 0xF && nn == 0x33, self.ld_bcd(x));
-        decode_instruction!("LD [I], Vx"   , c == 0xF && nn == 0x55, self.ld_into_mem(x));
-        decode_instruction!("LD Vx, [I]"   , c == 0xF && nn == 0x65, self.ld_from_mem(x));
-
-        self.set_pc(self.get_pc() + 2);
-    }
-
-    pub fn get_key_pressed(&self, key: u8) -> bool {
-        return self.memory[(KEY_FLAGS_ADDR + u16::from(key)) as usize] > 0
-    }
-
-    pub fn set_key_pressed(&mut self, key: u8, pressed: bool) {
-        self.memory[(KEY_FLAGS_ADDR + u16::from(key)) as usize] = if pressed { 1 } else { 0 };
-        if self.is_waiting_for_key() && pressed {
-            self.set_awaited_key(key);
-            self.clear_waiting_for_key();
-        }
-    }
-
-    pub fn is_waiting_for_key(&self) -> bool {
-        return self.memory[WAIT_KEY_PRESS_FLAG_ADDR as usize] & 0x80 > 0;
-    }
-
-    fn set_waiting_for_key(&mut self) {
-        self.memory[WAIT_KEY_PRESS_FLAG_ADDR as usize] |= 1 << 7;
-    }
-
-    fn clear_waiting_for_key(&mut self) {
-        self.memory[WAIT_KEY_PRESS_FLAG_ADDR as usize] &= !(1 << 7);
-    }
-
-    fn get_waiting_key_destination(&self) -> u8 {
-        return self.memory[WAIT_KEY_PRESS_FLAG_ADDR as usize] & 0xF;
-    }
-
-    fn set_waiting_key_destination(&mut self, x: u8) {
-        self.memory[WAIT_KEY_PRESS_FLAG_ADDR as usize] &= 0xF0;
-        self.memory[WAIT_KEY_PRESS_FLAG_ADDR as usize] |= x;
-    }
-
-    fn set_awaited_key(&mut self, key: u8) {
-        let x = self.get_waiting_key_destination();
-        self.set_v(x, key);
-    }
-
-    fn get_v(&self, index: u8) -> u8 {
-        return self.memory[(V_REGISTERS_ADDR + u16::from(index)) as usize];
-    }
-
-    fn set_v(&mut self, index: u8, value: u8) {
-        self.memory[(V_REGISTERS_ADDR + u16::from(index)) as usize] = value;
-    }
-    
-    fn get_dt(&self) -> u8 {
-        return self.memory[DELAY_TIMER_ADDR as usize];
-    }
-
-    fn set_dt(&mut self, value: u8) {
-        self.memory[DELAY_TIMER_ADDR as usize] = value;
-    }
-    
-    fn get_st(&self) -> u8 {
-        return self.memory[SOUND_TIMER_ADDR as usize];
-    }
-
-    fn set_st(&mut self, value: u8) {
-        self.memory[SOUND_TIMER_ADDR as usize] = value;
-    }
-    
-    fn get_i(&self) -> u16 {
-        return u16::from(self.memory[INDEX_REGISTER_ADDR as usize]) << 8 
-            | u16::from(self.memory[(INDEX_REGISTER_ADDR + 1) as usize]);
-    }
-
-    fn set_i(&mut self, value: u16) {
-        self.memory[INDEX_REGISTER_ADDR as usize] = (value >> 8 & 0xFF) as u8;
-        self.memory[(INDEX_REGISTER_ADDR + 1) as usize] = (value & 0xFF) as u8; 
-    }
-    
-    fn get_sp(&self) -> u8 {
-        return self.memory[STACK_POINTER_ADDR as usize];
-    }
-
-    fn set_sp(&mut self, value: u8) {
-        self.memory[STACK_POINTER_ADDR as usize] = value;
-    }
-
-    fn get_pc(&self) -> u16 {
-        return u16::from(self.memory[PROGRAM_COUNTER_ADDR as usize]) << 8 
-            | u16::from(self.memory[(PROGRAM_COUNTER_ADDR + 1) as usize])
-    }
-
-    fn set_pc(&mut self, value: u16) {
-        self.memory[PROGRAM_COUNTER_ADDR as usize] = (value >> 8 & 0xFF) as u8;
-        self.memory[(PROGRAM_COUNTER_ADDR + 1) as usize] = (value & 0xFF) as u8;
-    }
-
-    fn pop_from_stack(&mut self) -> u16 {
-        let offset = self.get_sp() * 2;
-        self.set_sp(self.get_sp() - 1);
-        return u16::from(self.memory[(STACK_ADDR + u16::from(offset)) as usize]) >> 8 
-            | u16::from(self.memory[(STACK_ADDR + u16::from(offset) + 1) as usize])
-    }
-
-    fn push_into_stack(&mut self, value: u16) {
-        self.set_sp(self.get_sp() + 1);
-        let offset = self.get_sp() * 2;
-        self.memory[(STACK_ADDR + u16::from(offset)) as usize] = (value >> 8 & 0xFF) as u8;
-        self.memory[(STACK_ADDR + u16::from(offset) + 1) as usize] = (value & 0xFF) as u8;
-    }
-
-    fn fetch_opcode(&self) -> u16 {
-        let pc = self.get_pc();
-        return u16::from(self.memory[pc as usize]) >> 8 
-            | u16::from(self.memory[(pc + 1) as usize]);
-    }
-
-    fn cls(&mut self) {
-        for index in 0..DISPLAY_BUFFER_SIZE {
-            self.memory[DISPLAY_BUFFER_ADDR as usize + index] = 0;
-        }
-    }
-
-    fn ret(&mut self) {
-        let addr = self.pop_from_stack();
-        self.set_pc(addr);
-    }
-
-    fn jmp_direct(&mut self, addr: u16) {
-        self.set_pc(addr);
-    }
-
-    fn call(&mut self, addr: u16) {
-        let current_pc = self.get_pc();
-        self.push_into_stack(current_pc);
-        self.set_pc(addr);
-    }
-
-    fn se_immedate(&mut self, x: u8, byte: u8) {
-        let vx = self.get_v(x);
-        if vx == byte {
-            self.set_pc(self.get_pc() + 2);
-        }
-    }
-
-    fn sne_immedate(&mut self, x: u8, byte: u8) {
-        let vx = self.get_v(x);
-        if vx != byte {
-            self.set_pc(self.get_pc() + 2);
-        }
-    }
-
-    fn se_registers(&mut self, x: u8, y: u8) {
-        let vx = self.get_v(x);
-        let vy = self.get_v(y);
-        if vx == vy {
-            self.set_pc(self.get_pc() + 2);
-        }
-    }
-
-    fn sne_registers(&mut self, x: u8, y: u8) {
-        let vx = self.get_v(x);
-        let vy = self.get_v(y);
-        if vx != vy {
-            self.set_pc(self.get_pc() + 2);
-        }
-    }
-
-    fn ld_immedate(&mut self, x: u8, byte: u8) {
-        self.set_v(x, byte);
-    }
-
-    fn add_immedate(&mut self, x: u8, byte: u8) {
-        let vx = self.get_v(x);
-        self.set_v(x, vx + byte);
-    }
-
-    fn ld_registers(&mut self, x: u8, y: u8) {
-        let vy = self.get_v(y);
-        self.set_v(x, vy);
-    }
-
-    fn or_registers(&mut self, x: u8, y: u8) {
-        let vx = self.get_v(x);
-        let vy = self.get_v(y);
-        self.set_v(x, vx | vy);
-    }
-
-    fn and_registers(&mut self, x: u8, y: u8) {
-        let vx = self.get_v(x);
-        let vy = self.get_v(y);
-        self.set_v(x, vx & vy);
-    }
-
-    fn xor_registers(&mut self, x: u8, y: u8) {
-        let vx = self.get_v(x);
-        let vy = self.get_v(y);
-        self.set_v(x, vx ^ vy);
-    }
-
-    fn add_registers(&mut self, x: u8, y: u8) {
-        let vx = self.get_v(x);
-        let vy = self.get_v(y);
-        self.set_v(0xF, if u16::from(vx) + u16::from(vy) > 0xFF { 1 } else { 0 });
-        self.set_v(x, vx + vy);
-    }
-
-    fn sub_registers(&mut self, x: u8, y: u8) {
-        let vx = self.get_v(x);
-        let vy = self.get_v(y);
-        self.set_v(0xF, if vx > vy { 1 } else { 0 });
-        self.set_v(x, vx - vy);
-    }
-
-    fn subn_registers(&mut self, x: u8, y: u8) {
-        let vx = self.get_v(x);
-        let vy = self.get_v(y);
-        self.set_v(0xF, if vy > vx { 1 } else { 0 });
-        self.set_v(x, vy - vx);
-    }
-
-    fn shr(&mut self, x: u8) {
-        let vx = self.get_v(x);
-        self.set_v(0xF, vx & 0x01);
-        self.set_v(x, vx >> 1);
-    }
-
-    fn shl(&mut self, x: u8) {
-        let vx = self.get_v(x);
-        self.set_v(0xF, vx >> 7);
-        self.set_v(x, vx << 1);
-    }
-
-    fn ld_index(&mut self, addr: u16) {
-        self.set_i(addr);
-    }
-
-    fn jmp_indirect(&mut self, addr: u16) {
-        let v0 = self.get_v(0);
-        self.set_pc(addr + u16::from(v0));
-    }
-
-    fn rnd(&mut self, x: u8, byte: u8) {
-        self.set_v(x, rand::random::<u8>() & byte);
-    }
-
-    fn drw(&mut self, x: u8, y: u8, n: u8) {
-        let put = |addr: u16, data: u8| {
-            self.memory[usize::from(DISPLAY_BUFFER_ADDR) + usize::from(addr)] ^= data;
-            return (self.memory[usize::from(DISPLAY_BUFFER_ADDR) + usize::from(addr)] ^ data) & data;
-        };
-
-        let mut overflow = 0;
-        let vx = self.get_v(x);
-        let vy = self.get_v(y);
-        let index = self.get_i();
-        const WIDTH: u8 = DISPLAY_WIDTH as u8;
-        const HEIGHT: u8 = DISPLAY_HEIGHT as u8;
-        
-        for i in 0..n {
-            let addr_left = u16::from(vx % WIDTH + (vy + i) % HEIGHT * WIDTH / 8);
-            let addr_right = u16::from((vx + 7) % WIDTH + (vy + i) % HEIGHT * WIDTH / 8);
-
-            let data_left = self.memory[usize::from(index) + usize::from(i)] >> vx % 8;
-            let data_right = self.memory[usize::from(index) + usize::from(i)] << 8 - vx % 8;
-
-            self.memory[usize::from(DISPLAY_BUFFER_ADDR) + usize::from(addr_left)] ^= data_left;
-            overflow |= (self.memory[usize::from(DISPLAY_BUFFER_ADDR) + usize::from(addr_left)] ^ data_left) & data_left;
-
-            self.memory[usize::from(DISPLAY_BUFFER_ADDR) + usize::from(addr_right)] ^= data_right;
-            overflow |= (self.memory[usize::from(DISPLAY_BUFFER_ADDR) + usize::from(addr_right)] ^ data_right) & data_right;
-        }
-
-        self.set_v(0xF, if overflow != 0 { 1 } else { 0 });
-    }
-
-    fn skp(&mut self, x: u8) {
-        let vx = self.get_v(x);
-        if self.get_key_pressed(vx) {
-            self.set_pc(self.get_pc() + 2);
-        }
-    }
-
-    fn sknp(&mut self, x: u8) {
-        let vx = self.get_v(x);
-        if !self.get_key_pressed(vx) {
-            self.set_pc(self.get_pc() + 2);
-        }
-    }
-
-    fn ld_from_dt(&mut self, x: u8) {
-        self.set_v(x, self.get_dt());
-    }
-
-    fn ld_key_press(&mut self, x: u8) {
-        self.set_waiting_key_destination(x);
-        self.set_waiting_for_key();
-    }
-
-    fn ld_into_dt(&mut self, x: u8) {
-        let vx = self.get_v(x);
-        self.set_dt(vx);
-    }
-
-    fn ld_into_st(&mut self, x: u8) {
-        let vx = self.get_v(x);
-        self.set_st(vx);
-    }
-
-    fn add_index(&mut self, x: u8) {
-        let vx = self.get_v(x);
-        let i = self.get_i();
-        self.set_i(i + u16::from(vx));
-    }
-
-    fn ld_font_addr(&mut self, x: u8) {
-        let vx = self.get_v(x);
-        self.set_i(FONT_ADDR + u16::from(vx) * 5);
-    }
-
-    fn ld_bcd(&mut self, x: u8) {
-        let vx = self.get_v(x);
-        let i = self.get_i();
-        self.memory[i as usize] = vx / 100 % 10;
-        self.memory[i as usize + 1] = vx / 10 % 10;
-        self.memory[i as usize + 2] = vx % 10;
-    }
-
-    fn ld_into_mem(&mut self, x: u8) {
-        let i = self.get_i();
-        for v_reg_index in 0..=x {
-            self.memory[(i + u16::from(v_reg_index)) as usize] = self.get_v(v_reg_index);
-        }
-    }
-
-    fn ld_from_mem(&mut self, x: u8) {
-        let i = self.get_i();
-        for v_reg_index in 0..=x {
-            self.set_v(v_reg_index, self.memory[(i + u16::from(v_reg_index)) as usize])
-        }
-    }
+/*
+Chip8 memory model looks like this: 
+
++----------------+= 0xFFF (4095) End of Chip-8 RAM
+|                |
+|                |
+|                |
+|                |
+|                |
+| 0x200 to 0xFFF | 
+|     Chip-8     |
+| Program / Data |
+|     Space      |
+|                |
+|                |
+|                |
+|                |
+|                |
+|                |
+|                |
++----------------+= 0x200 (512) Start of Chip-8 programs
+|                |
+| Reserved for   |
+|  interpreter   |
++----------------+= 0x000 (0) Start of Chip-8 RAM
+
+Because Chip8 is a Virtual Machine, space reserved for interpreter is the place 
+where all the Chip8 internals should be (registers, stack, display memory and other stuff used by specific VM implementation)
+
+Internals required by specification:
+- 16 8-bit general purpose registers: V0...VF
+- 16-bit index register
+- 8-bit Delay Timer
+- 8-bit Sound Timer
+- 16-bit Program Counter
+- 8-bit Stack Pointer
+- Stack allowing 16-levels of nested subroutines
+- Display buffer for monochromatic 64px x 32px display
+- Built-in font sprites
+
+After calculating space required by above internals 
+we can calculate how much space we've left for our custom implementation specific Chip8 elements.
+
+256 bytes - Display buffer
+ 80 bytes - Built-in font
+ 32 bytes - Stack
+ 16 bytes - General purpose registers
+  2 bytes - Index register
+  2 bytes - Program counter
+  1 byte  - Delay Timer
+  1 byte  - Sound Timer
+  1 byte  - Stack Pointer
+----------------------------------------
+391 bytes - Total
+
+512 - 391 = 121 bytes
+ 
+This implementation will have following memory mapping of the internals:
+0x000 - 0x04F : Built-in font
+0x050 - 0x05F : V registers
+0x060 - 0x07F : Stack
+0x080 - 0x17F : Display buffer
+0x180         : Stack pointer
+0x181         : Sound timer
+0x182         : Delay timer
+0x183 - 0x184 : Program counter
+0x185 - 0x186 : Index register
+0x187         : [CUSTOM] Wait key press flag (If most significant bit is indicating if VM is waiting for key, 4 least significant bits tells to which register save the key)
+0x188 - 0x198 : [CUSTOM] Key presses flags
+*/
+
+use std::collections::HashMap;
+
+use wasm_bindgen::prelude::*;
+
+use crate::recompiler::{self, Block, MicroOp};
+
+macro_rules! decode_instruction {
+    ($mnemonic:literal, $condition:expr, $op:expr) => {
+        if $condition {
+            $op;
+        }
+    }
+}
+
+const FONT: [u32; 16] = [
+    0xF999F, 0x26227, 0xF1F8F, 0xF1F1F,
+    0x99F11, 0xF8F1F, 0xF8F9F, 0xF1244,
+    0xF9F9F, 0xF9F1F, 0xF9F99, 0xE9E9E,
+    0xF888F, 0xE999E, 0xF8F8F, 0xF8F88
+];
+
+const FONT_ADDR: u16                = 0x0000;
+const V_REGISTERS_ADDR: u16         = 0x0050;
+const STACK_ADDR: u16               = 0x0060;
+const DISPLAY_BUFFER_ADDR: u16      = 0x0080;
+const STACK_POINTER_ADDR: u16       = 0x0180;
+const SOUND_TIMER_ADDR: u16         = 0x0181;
+const DELAY_TIMER_ADDR: u16         = 0x0182;
+const PROGRAM_COUNTER_ADDR: u16     = 0x0183;
+const INDEX_REGISTER_ADDR: u16      = 0x0185;
+const WAIT_KEY_PRESS_FLAG_ADDR: u16 = 0x0187;
+const KEY_FLAGS_ADDR: u16           = 0x0188;
+
+const PROGRAM_ADDR: u16 = 0x0200;
+const PROGRAM_SPACE_SIZE: usize = 0x1000 - 0x0200;
+
+const SAVE_STATE_MAGIC: [u8; 4] = *b"C8SS";
+const SAVE_STATE_VERSION: u8 = 1;
+const SAVE_STATE_HEADER_SIZE: usize = SAVE_STATE_MAGIC.len() + 1;
+
+const DISPLAY_WIDTH: usize = 64;
+const DISPLAY_HEIGHT: usize = 32;
+const DISPLAY_BUFFER_SIZE: usize = DISPLAY_HEIGHT * DISPLAY_WIDTH / 8;
+
+pub struct Chip8 {
+    memory: [u8; 0x1000],
+    /// Decoded blocks, keyed by their start address.
+    block_cache: HashMap<u16, Block>,
+    /// `(block_start, next_pc)` for the block currently being walked
+    /// instruction-by-instruction, if the last `execute_instruction` call
+    /// left off inside one. A cached block's micro-ops are folded assuming
+    /// every earlier op in the block already ran, so they may only be
+    /// replayed by indexing into `ops` while this cursor confirms PC is
+    /// advancing sequentially from that same block's start; a PC that lands
+    /// mid-block any other way (a jump target) is always decoded fresh.
+    cursor: Option<(u16, u16)>
+}
+
+/// Reported by `tick_timers` when the sound timer crosses the buzzing
+/// threshold, so the frontend can gate a `WebAudio` oscillator on/off
+/// instead of polling `is_buzzing` every frame.
+#[wasm_bindgen]
+#[derive(PartialEq)]
+pub enum BuzzerTransition {
+    None,
+    Started,
+    Stopped,
+}
+
+impl Chip8 {
+    pub fn get_display_buffer(&self) -> [u8; DISPLAY_BUFFER_SIZE] {
+        let start = usize::from(DISPLAY_BUFFER_ADDR);
+        let end: usize = usize::from(DISPLAY_BUFFER_ADDR) + usize::from(DISPLAY_BUFFER_SIZE);
+        return self.memory[start..end]
+            .try_into()
+            .expect("Slice containing display buffer has incorrect length");
+    }
+
+    /// Decrements both timers and reports how the buzzer state changed,
+    /// relative to `was_buzzing` as it stood before this frame's cycles ran
+    /// (a same-frame `LD ST, Vx` can already have raised the sound timer by
+    /// the time this runs, so that can't be sampled here).
+    fn tick_timers(&mut self, was_buzzing: bool) -> BuzzerTransition {
+        self.set_dt(self.get_dt().saturating_sub(1));
+        self.set_st(self.get_st().saturating_sub(1));
+
+        return match (was_buzzing, self.is_buzzing()) {
+            (false, true) => BuzzerTransition::Started,
+            (true, false) => BuzzerTransition::Stopped,
+            _ => BuzzerTransition::None,
+        };
+    }
+
+    fn set_waiting_for_key(&mut self) {
+        self.memory[WAIT_KEY_PRESS_FLAG_ADDR as usize] |= 1 << 7;
+    }
+
+    fn clear_waiting_for_key(&mut self) {
+        self.memory[WAIT_KEY_PRESS_FLAG_ADDR as usize] &= !(1 << 7);
+    }
+
+    fn get_waiting_key_destination(&self) -> u8 {
+        return self.memory[WAIT_KEY_PRESS_FLAG_ADDR as usize] & 0xF;
+    }
+
+    fn set_waiting_key_destination(&mut self, x: u8) {
+        self.memory[WAIT_KEY_PRESS_FLAG_ADDR as usize] &= 0xF0;
+        self.memory[WAIT_KEY_PRESS_FLAG_ADDR as usize] |= x;
+    }
+
+    fn set_awaited_key(&mut self, key: u8) {
+        let x = self.get_waiting_key_destination();
+        self.set_v(x, key);
+    }
+
+    fn get_v(&self, index: u8) -> u8 {
+        return self.memory[(V_REGISTERS_ADDR + u16::from(index)) as usize];
+    }
+
+    fn set_v(&mut self, index: u8, value: u8) {
+        self.memory[(V_REGISTERS_ADDR + u16::from(index)) as usize] = value;
+    }
+    
+    fn get_dt(&self) -> u8 {
+        return self.memory[DELAY_TIMER_ADDR as usize];
+    }
+
+    fn set_dt(&mut self, value: u8) {
+        self.memory[DELAY_TIMER_ADDR as usize] = value;
+    }
+    
+    fn get_st(&self) -> u8 {
+        return self.memory[SOUND_TIMER_ADDR as usize];
+    }
+
+    fn set_st(&mut self, value: u8) {
+        self.memory[SOUND_TIMER_ADDR as usize] = value;
+    }
+    
+    fn get_i(&self) -> u16 {
+        return u16::from(self.memory[INDEX_REGISTER_ADDR as usize]) << 8 
+            | u16::from(self.memory[(INDEX_REGISTER_ADDR + 1) as usize]);
+    }
+
+    fn set_i(&mut self, value: u16) {
+        self.memory[INDEX_REGISTER_ADDR as usize] = (value >> 8 & 0xFF) as u8;
+        self.memory[(INDEX_REGISTER_ADDR + 1) as usize] = (value & 0xFF) as u8; 
+    }
+    
+    fn get_sp(&self) -> u8 {
+        return self.memory[STACK_POINTER_ADDR as usize];
+    }
+
+    fn set_sp(&mut self, value: u8) {
+        self.memory[STACK_POINTER_ADDR as usize] = value;
+    }
+
+    fn get_pc(&self) -> u16 {
+        return u16::from(self.memory[PROGRAM_COUNTER_ADDR as usize]) << 8 
+            | u16::from(self.memory[(PROGRAM_COUNTER_ADDR + 1) as usize])
+    }
+
+    fn set_pc(&mut self, value: u16) {
+        self.memory[PROGRAM_COUNTER_ADDR as usize] = (value >> 8 & 0xFF) as u8;
+        self.memory[(PROGRAM_COUNTER_ADDR + 1) as usize] = (value & 0xFF) as u8;
+    }
+
+    fn pop_from_stack(&mut self) -> u16 {
+        let offset = self.get_sp() * 2;
+        self.set_sp(self.get_sp() - 1);
+        return u16::from(self.memory[(STACK_ADDR + u16::from(offset)) as usize]) << 8
+            | u16::from(self.memory[(STACK_ADDR + u16::from(offset) + 1) as usize])
+    }
+
+    fn push_into_stack(&mut self, value: u16) {
+        self.set_sp(self.get_sp() + 1);
+        let offset = self.get_sp() * 2;
+        self.memory[(STACK_ADDR + u16::from(offset)) as usize] = (value >> 8 & 0xFF) as u8;
+        self.memory[(STACK_ADDR + u16::from(offset) + 1) as usize] = (value & 0xFF) as u8;
+    }
+
+    fn fetch_opcode(&self) -> u16 {
+        let pc = self.get_pc();
+        return u16::from(self.memory[pc as usize]) << 8
+            | u16::from(self.memory[(pc + 1) as usize]);
+    }
+
+    fn cls(&mut self) {
+        for index in 0..DISPLAY_BUFFER_SIZE {
+            self.memory[DISPLAY_BUFFER_ADDR as usize + index] = 0;
+        }
+    }
+
+    fn ret(&mut self) {
+        let addr = self.pop_from_stack();
+        self.set_pc(addr);
+    }
+
+    fn jmp_direct(&mut self, addr: u16) {
+        self.set_pc(addr);
+    }
+
+    fn call(&mut self, addr: u16) {
+        let current_pc = self.get_pc();
+        self.push_into_stack(current_pc);
+        self.set_pc(addr);
+    }
+
+    fn se_immedate(&mut self, x: u8, byte: u8) {
+        let vx = self.get_v(x);
+        if vx == byte {
+            self.set_pc(self.get_pc() + 2);
+        }
+    }
+
+    fn sne_immedate(&mut self, x: u8, byte: u8) {
+        let vx = self.get_v(x);
+        if vx != byte {
+            self.set_pc(self.get_pc() + 2);
+        }
+    }
+
+    fn se_registers(&mut self, x: u8, y: u8) {
+        let vx = self.get_v(x);
+        let vy = self.get_v(y);
+        if vx == vy {
+            self.set_pc(self.get_pc() + 2);
+        }
+    }
+
+    fn sne_registers(&mut self, x: u8, y: u8) {
+        let vx = self.get_v(x);
+        let vy = self.get_v(y);
+        if vx != vy {
+            self.set_pc(self.get_pc() + 2);
+        }
+    }
+
+    fn ld_immedate(&mut self, x: u8, byte: u8) {
+        self.set_v(x, byte);
+    }
+
+    fn add_immedate(&mut self, x: u8, byte: u8) {
+        let vx = self.get_v(x);
+        self.set_v(x, vx + byte);
+    }
+
+    fn ld_registers(&mut self, x: u8, y: u8) {
+        let vy = self.get_v(y);
+        self.set_v(x, vy);
+    }
+
+    fn or_registers(&mut self, x: u8, y: u8) {
+        let vx = self.get_v(x);
+        let vy = self.get_v(y);
+        self.set_v(x, vx | vy);
+    }
+
+    fn and_registers(&mut self, x: u8, y: u8) {
+        let vx = self.get_v(x);
+        let vy = self.get_v(y);
+        self.set_v(x, vx & vy);
+    }
+
+    fn xor_registers(&mut self, x: u8, y: u8) {
+        let vx = self.get_v(x);
+        let vy = self.get_v(y);
+        self.set_v(x, vx ^ vy);
+    }
+
+    fn add_registers(&mut self, x: u8, y: u8) {
+        let vx = self.get_v(x);
+        let vy = self.get_v(y);
+        self.set_v(0xF, if u16::from(vx) + u16::from(vy) > 0xFF { 1 } else { 0 });
+        self.set_v(x, vx + vy);
+    }
+
+    fn sub_registers(&mut self, x: u8, y: u8) {
+        let vx = self.get_v(x);
+        let vy = self.get_v(y);
+        self.set_v(0xF, if vx > vy { 1 } else { 0 });
+        self.set_v(x, vx - vy);
+    }
+
+    fn subn_registers(&mut self, x: u8, y: u8) {
+        let vx = self.get_v(x);
+        let vy = self.get_v(y);
+        self.set_v(0xF, if vy > vx { 1 } else { 0 });
+        self.set_v(x, vy - vx);
+    }
+
+    fn shr(&mut self, x: u8) {
+        let vx = self.get_v(x);
+        self.set_v(0xF, vx & 0x01);
+        self.set_v(x, vx >> 1);
+    }
+
+    fn shl(&mut self, x: u8) {
+        let vx = self.get_v(x);
+        self.set_v(0xF, vx >> 7);
+        self.set_v(x, vx << 1);
+    }
+
+    fn ld_index(&mut self, addr: u16) {
+        self.set_i(addr);
+    }
+
+    fn jmp_indirect(&mut self, addr: u16) {
+        let v0 = self.get_v(0);
+        self.set_pc(addr + u16::from(v0));
+    }
+
+    fn rnd(&mut self, x: u8, byte: u8) {
+        self.set_v(x, rand::random::<u8>() & byte);
+    }
+
+    fn drw(&mut self, x: u8, y: u8, n: u8) {
+        let put = |addr: u16, data: u8| {
+            self.memory[usize::from(DISPLAY_BUFFER_ADDR) + usize::from(addr)] ^= data;
+            return (self.memory[usize::from(DISPLAY_BUFFER_ADDR) + usize::from(addr)] ^ data) & data;
+        };
+
+        let mut overflow = 0;
+        let vx = self.get_v(x);
+        let vy = self.get_v(y);
+        let index = self.get_i();
+        const WIDTH: u8 = DISPLAY_WIDTH as u8;
+        const HEIGHT: u8 = DISPLAY_HEIGHT as u8;
+        
+        for i in 0..n {
+            let addr_left = u16::from(vx % WIDTH + (vy + i) % HEIGHT * WIDTH / 8);
+            let addr_right = u16::from((vx + 7) % WIDTH + (vy + i) % HEIGHT * WIDTH / 8);
+
+            let data_left = self.memory[usize::from(index) + usize::from(i)] >> vx % 8;
+            let data_right = self.memory[usize::from(index) + usize::from(i)] << 8 - vx % 8;
+
+            self.memory[usize::from(DISPLAY_BUFFER_ADDR) + usize::from(addr_left)] ^= data_left;
+            overflow |= (self.memory[usize::from(DISPLAY_BUFFER_ADDR) + usize::from(addr_left)] ^ data_left) & data_left;
+
+            self.memory[usize::from(DISPLAY_BUFFER_ADDR) + usize::from(addr_right)] ^= data_right;
+            overflow |= (self.memory[usize::from(DISPLAY_BUFFER_ADDR) + usize::from(addr_right)] ^ data_right) & data_right;
+        }
+
+        self.set_v(0xF, if overflow != 0 { 1 } else { 0 });
+    }
+
+    fn skp(&mut self, x: u8) {
+        let vx = self.get_v(x);
+        if self.get_key_pressed(vx) {
+            self.set_pc(self.get_pc() + 2);
+        }
+    }
+
+    fn sknp(&mut self, x: u8) {
+        let vx = self.get_v(x);
+        if !self.get_key_pressed(vx) {
+            self.set_pc(self.get_pc() + 2);
+        }
+    }
+
+    fn ld_from_dt(&mut self, x: u8) {
+        self.set_v(x, self.get_dt());
+    }
+
+    fn ld_key_press(&mut self, x: u8) {
+        self.set_waiting_key_destination(x);
+        self.set_waiting_for_key();
+    }
+
+    fn ld_into_dt(&mut self, x: u8) {
+        let vx = self.get_v(x);
+        self.set_dt(vx);
+    }
+
+    fn ld_into_st(&mut self, x: u8) {
+        let vx = self.get_v(x);
+        self.set_st(vx);
+    }
+
+    fn add_index(&mut self, x: u8) {
+        let vx = self.get_v(x);
+        let i = self.get_i();
+        self.set_i(i + u16::from(vx));
+    }
+
+    fn ld_font_addr(&mut self, x: u8) {
+        let vx = self.get_v(x);
+        self.set_i(FONT_ADDR + u16::from(vx) * 5);
+    }
+
+    fn ld_bcd(&mut self, x: u8) {
+        let vx = self.get_v(x);
+        let i = self.get_i();
+        self.memory[i as usize] = vx / 100 % 10;
+        self.memory[i as usize + 1] = vx / 10 % 10;
+        self.memory[i as usize + 2] = vx % 10;
+        self.invalidate_blocks_overlapping(i, i + 3);
+    }
+
+    fn ld_into_mem(&mut self, x: u8) {
+        let i = self.get_i();
+        for v_reg_index in 0..=x {
+            self.memory[(i + u16::from(v_reg_index)) as usize] = self.get_v(v_reg_index);
+        }
+        self.invalidate_blocks_overlapping(i, i + u16::from(x) + 1);
+    }
+
+    /// Drops any cached block whose `[start, end)` range overlaps the
+    /// `[start, end)` span just written by self-modifying code, so stale
+    /// decoded micro-ops are never replayed against the new bytes.
+    fn invalidate_blocks_overlapping(&mut self, start: u16, end: u16) {
+        self.block_cache.retain(|_, block| block.start >= end || start >= block.end);
+
+        if let Some((cursor_start, _)) = self.cursor {
+            if !self.block_cache.contains_key(&cursor_start) {
+                self.cursor = None;
+            }
+        }
+    }
+
+    fn ld_from_mem(&mut self, x: u8) {
+        let i = self.get_i();
+        for v_reg_index in 0..=x {
+            self.set_v(v_reg_index, self.memory[(i + u16::from(v_reg_index)) as usize])
+        }
+    }
+
+    /// Pure size check behind `load_rom`'s rejection, kept separate from the
+    /// `JsValue` it's reported through so it can be unit tested directly.
+    fn validate_rom_size(bytes: &[u8]) -> Result<(), &'static str> {
+        if bytes.len() > PROGRAM_SPACE_SIZE {
+            return Err("ROM does not fit in the 0x200-0xFFF program/data space");
+        }
+
+        return Ok(());
+    }
+
+    /// Header/length checks behind `load_state`'s rejection, kept separate
+    /// from the `JsValue` it's reported through so it can be unit tested
+    /// directly.
+    fn validate_save_state(data: &[u8]) -> Result<(), &'static str> {
+        if data.len() != SAVE_STATE_HEADER_SIZE + 0x1000 {
+            return Err("Save state has an incorrect length");
+        }
+        if data[..SAVE_STATE_MAGIC.len()] != SAVE_STATE_MAGIC {
+            return Err("Save state is missing the expected magic bytes");
+        }
+        if data[SAVE_STATE_MAGIC.len()] != SAVE_STATE_VERSION {
+            return Err("Save state was produced by an incompatible version");
+        }
+
+        return Ok(());
+    }
+
+    fn cache_block(&mut self, start: u16) {
+        let block = recompiler::decode_block(&self.memory, start);
+        self.block_cache.insert(start, block);
+    }
+
+    fn apply_micro_op(&mut self, op: MicroOp) {
+        match op {
+            MicroOp::SetV(x, imm) => self.ld_immedate(x, imm),
+            MicroOp::MovV(x, y) => self.ld_registers(x, y),
+            MicroOp::OrV(x, y) => self.or_registers(x, y),
+            MicroOp::AndV(x, y) => self.and_registers(x, y),
+            MicroOp::XorV(x, y) => self.xor_registers(x, y),
+            MicroOp::AddVImm(x, imm) => self.add_immedate(x, imm),
+            MicroOp::LoadI(nnn) => self.ld_index(nnn),
+            MicroOp::Raw(opcode) => self.execute_opcode(opcode),
+            MicroOp::Dead => {}
+        }
+    }
+
+    fn execute_opcode(&mut self, opcode: u16) {
+        let c = opcode >> 12 & 0xF;
+        let nnn: u16 = opcode & 0xFFF;
+        let nn: u8 = (opcode & 0xFF) as u8;
+        let n: u8 = (opcode & 0xF) as u8;
+        let x: u8 = (opcode >> 8 & 0xF) as u8;
+        let y: u8 = (opcode >> 4 & 0xF) as u8;
+
+        decode_instruction!("CLS"          , c == 0x0 && nn == 0xE0, self.cls());
+        decode_instruction!("RET"          , c == 0x0 && nn == 0xEE, self.ret());
+        decode_instruction!("JMP nnn"      , c == 0x1              , self.jmp_direct(nnn));
+        decode_instruction!("CALL nnn"     , c == 0x2              , self.call(nnn));
+        decode_instruction!("SE Vx, nn"    , c == 0x3              , self.se_immedate(x, nn));
+        decode_instruction!("SNE Vx, nn"   , c == 0x4              , self.sne_immedate(x, nn));
+        decode_instruction!("SE Vx, Vy"    , c == 0x5 && n == 0x0  , self.se_registers(x, y));
+        decode_instruction!("LD Vx, nn"    , c == 0x6              , self.ld_immedate(x, nn));
+        decode_instruction!("ADD Vx, nn"   , c == 0x7              , self.add_immedate(x, nn));
+        decode_instruction!("LD Vx, Vy"    , c == 0x8 && n == 0x0  , self.ld_registers(x, y));
+        decode_instruction!("OR Vx, Vy"    , c == 0x8 && n == 0x1  , self.or_registers(x, y));
+        decode_instruction!("AND Vx, Vy"   , c == 0x8 && n == 0x2  , self.and_registers(x, y));
+        decode_instruction!("XOR Vx, Vy"   , c == 0x8 && n == 0x3  , self.xor_registers(x, y));
+        decode_instruction!("ADD Vx, Vy"   , c == 0x8 && n == 0x4  , self.add_registers(x, y));
+        decode_instruction!("SUB Vx, Vy"   , c == 0x8 && n == 0x5  , self.sub_registers(x, y));
+        decode_instruction!("SHR Vx"       , c == 0x8 && n == 0x6  , self.shr(x));
+        decode_instruction!("SUBN Vx, Vy"  , c == 0x8 && n == 0x7  , self.subn_registers(x, y));
+        decode_instruction!("SHL Vx"       , c == 0x8 && n == 0xE  , self.shl(x));
+        decode_instruction!("SNE Vx, Vy"   , c == 0x9 && n == 0x0  , self.sne_registers(x, y));
+        decode_instruction!("LD I, nnn"    , c == 0xA              , self.ld_index(nnn));
+        decode_instruction!("JMP V0, nnn"  , c == 0xB              , self.jmp_indirect(nnn));
+        decode_instruction!("RND Vx, nn"   , c == 0xC              , self.rnd(x, nn));
+        decode_instruction!("DRW Vx, Vy, n", c == 0xD              , self.drw(x, y, n));
+        decode_instruction!("SKP Vx"       , c == 0xE && nn == 0x9E, self.skp(x));
+        decode_instruction!("SKNP Vx"      , c == 0xE && nn == 0xA1, self.sknp(x));
+        decode_instruction!("LD Vx, DT"    , c == 0xF && nn == 0x07, self.ld_from_dt(x));
+        decode_instruction!("LD Vx, K"     , c == 0xF && nn == 0x0A, self.ld_key_press(x));
+        decode_instruction!("LD DT, Vx"    , c == 0xF && nn == 0x15, self.ld_into_dt(x));
+        decode_instruction!("LD ST, Vx"    , c == 0xF && nn == 0x18, self.ld_into_st(x));
+        decode_instruction!("ADD I, Vx"    , c == 0xF && nn == 0x1E, self.add_index(x));
+        decode_instruction!("LD F, Vx"     , c == 0xF && nn == 0x29, self.ld_font_addr(x));
+        decode_instruction!("LD B, Vx"     , c == 0xF && nn == 0x33, self.ld_bcd(x));
+        decode_instruction!("LD [I], Vx"   , c == 0xF && nn == 0x55, self.ld_into_mem(x));
+        decode_instruction!("LD Vx, [I]"   , c == 0xF && nn == 0x65, self.ld_from_mem(x));
+    }
+}
+
+#[wasm_bindgen]
+impl Chip8 {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        let mut chip8 = Self{ memory: [0; 0x1000], block_cache: HashMap::new(), cursor: None };
+
+        // Install font
+        for digit in 0..16 {
+            for byte in 0..5 {
+                chip8.memory[digit * byte] = (FONT[digit] >> (16 - byte * 4) & 0xFF) as u8;
+            }
+        }
+
+        return chip8;
+    }
+
+    /// Runs exactly the one instruction at the current PC, same as before
+    /// the recompiler existed. A cached block's micro-ops are only ever
+    /// applied by direct index while `cursor` confirms this call is the
+    /// in-order continuation of that same block's own start; any other
+    /// arrival at a PC (a jump, including one that lands inside another
+    /// block's span) decodes a fresh block beginning exactly there instead
+    /// of reusing ops folded for a different entry point.
+    pub fn execute_instruction(&mut self) {
+        if self.is_waiting_for_key() {
+            return;
+        }
+
+        let pc = self.get_pc();
+        let block_start = match self.cursor {
+            Some((start, next)) if next == pc && self.block_cache.contains_key(&start) => start,
+            _ => {
+                if !self.block_cache.contains_key(&pc) {
+                    self.cache_block(pc);
+                }
+                pc
+            }
+        };
+
+        let block = &self.block_cache[&block_start];
+        if pc < block.end {
+            let op = block.ops[usize::from((pc - block_start) / 2)];
+            self.cursor = Some((block_start, pc + 2));
+            self.apply_micro_op(op);
+        } else {
+            self.cursor = None;
+            let opcode = self.fetch_opcode();
+            self.execute_opcode(opcode);
+        }
+
+        self.set_pc(self.get_pc() + 2);
+    }
+
+    pub fn get_key_pressed(&self, key: u8) -> bool {
+        return self.memory[(KEY_FLAGS_ADDR + u16::from(key)) as usize] > 0
+    }
+
+    pub fn set_key_pressed(&mut self, key: u8, pressed: bool) {
+        self.memory[(KEY_FLAGS_ADDR + u16::from(key)) as usize] = if pressed { 1 } else { 0 };
+        if self.is_waiting_for_key() && pressed {
+            self.set_awaited_key(key);
+            self.clear_waiting_for_key();
+        }
+    }
+
+    pub fn is_waiting_for_key(&self) -> bool {
+        return self.memory[WAIT_KEY_PRESS_FLAG_ADDR as usize] & 0x80 > 0;
+    }
+
+    /// Copies `bytes` into the 0x200-0xFFF program/data space and points the
+    /// program counter at the start of the ROM. Rejects ROMs that don't fit.
+    pub fn load_rom(&mut self, bytes: &[u8]) -> Result<(), JsValue> {
+        Self::validate_rom_size(bytes).map_err(JsValue::from_str)?;
+
+        let start = usize::from(PROGRAM_ADDR);
+        self.memory[start..start + bytes.len()].copy_from_slice(bytes);
+        self.set_pc(PROGRAM_ADDR);
+        self.block_cache.clear();
+        self.cursor = None;
+
+        return Ok(());
+    }
+
+    /// Zeroes everything but the built-in font, so a fresh ROM can be loaded
+    /// into a clean VM without re-creating the `Chip8` instance.
+    pub fn reset(&mut self) {
+        for addr in usize::from(V_REGISTERS_ADDR)..self.memory.len() {
+            self.memory[addr] = 0;
+        }
+        self.block_cache.clear();
+        self.cursor = None;
+    }
+
+    /// Runs `cycles_per_frame` CPU cycles, then ticks the delay/sound timers
+    /// once, matching the real hardware's ~500-700Hz CPU against 60Hz timers.
+    /// Meant to be called once per `requestAnimationFrame`.
+    pub fn step_frame(&mut self, cycles_per_frame: usize) -> BuzzerTransition {
+        let was_buzzing = self.is_buzzing();
+
+        for _ in 0..cycles_per_frame {
+            self.execute_instruction();
+        }
+
+        return self.tick_timers(was_buzzing);
+    }
+
+    /// True while the sound timer is non-zero, i.e. the real hardware would
+    /// be buzzing.
+    pub fn is_buzzing(&self) -> bool {
+        return self.get_st() > 0;
+    }
+
+    /// Dumps the full 4KB memory image, prefixed with a magic/version header
+    /// so a future layout change can detect and reject an incompatible save.
+    pub fn serialize_state(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(SAVE_STATE_HEADER_SIZE + self.memory.len());
+        data.extend_from_slice(&SAVE_STATE_MAGIC);
+        data.push(SAVE_STATE_VERSION);
+        data.extend_from_slice(&self.memory);
+
+        return data;
+    }
+
+    /// Restores a state produced by `serialize_state`, rejecting data with a
+    /// missing/mismatched header or the wrong length.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), JsValue> {
+        Self::validate_save_state(data).map_err(JsValue::from_str)?;
+
+        self.memory.copy_from_slice(&data[SAVE_STATE_HEADER_SIZE..]);
+        self.block_cache.clear();
+        self.cursor = None;
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_rom_rejects_a_rom_that_does_not_fit_in_the_program_space() {
+        let oversized_rom = vec![0u8; PROGRAM_SPACE_SIZE + 1];
+
+        assert!(Chip8::validate_rom_size(&oversized_rom).is_err());
+    }
+
+    #[test]
+    fn load_rom_accepts_a_rom_that_exactly_fills_the_program_space() {
+        let mut chip8 = Chip8::new();
+        let full_size_rom = vec![0u8; PROGRAM_SPACE_SIZE];
+
+        assert!(chip8.load_rom(&full_size_rom).is_ok());
+        assert_eq!(chip8.get_pc(), PROGRAM_ADDR);
+    }
+
+    #[test]
+    fn reset_zeroes_registers_and_timers_without_touching_the_font() {
+        let mut chip8 = Chip8::new();
+        chip8.set_v(0, 42);
+        chip8.set_dt(10);
+        chip8.set_st(10);
+        let font_before_reset = chip8.memory[..V_REGISTERS_ADDR as usize].to_vec();
+
+        chip8.reset();
+
+        assert_eq!(chip8.get_v(0), 0);
+        assert_eq!(chip8.get_dt(), 0);
+        assert_eq!(chip8.get_st(), 0);
+        assert_eq!(chip8.memory[..V_REGISTERS_ADDR as usize], font_before_reset[..]);
+    }
+
+    #[test]
+    fn serialize_then_load_state_round_trips_the_memory_image() {
+        let mut chip8 = Chip8::new();
+        chip8.set_v(0, 42);
+
+        let data = chip8.serialize_state();
+
+        let mut restored = Chip8::new();
+        assert!(restored.load_state(&data).is_ok());
+        assert_eq!(restored.get_v(0), 42);
+    }
+
+    #[test]
+    fn load_state_rejects_data_with_the_wrong_length() {
+        let too_short = vec![0u8; SAVE_STATE_HEADER_SIZE + 0x1000 - 1];
+
+        assert!(Chip8::validate_save_state(&too_short).is_err());
+    }
+
+    #[test]
+    fn load_state_rejects_data_with_a_bad_magic() {
+        let chip8 = Chip8::new();
+        let mut data = chip8.serialize_state();
+        data[0] = !data[0];
+
+        assert!(Chip8::validate_save_state(&data).is_err());
+    }
+
+    #[test]
+    fn load_state_rejects_data_with_an_incompatible_version() {
+        let chip8 = Chip8::new();
+        let mut data = chip8.serialize_state();
+        data[SAVE_STATE_MAGIC.len()] = SAVE_STATE_VERSION + 1;
+
+        assert!(Chip8::validate_save_state(&data).is_err());
+    }
+
+    #[test]
+    fn step_frame_runs_exactly_cycles_per_frame_instructions() {
+        // ADD V0, 1 (x3), with no control-flow opcode to end the block early.
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(&[0x70, 0x01, 0x70, 0x01, 0x70, 0x01]).unwrap();
+
+        chip8.step_frame(3);
+
+        assert_eq!(chip8.get_v(0), 3);
+    }
+
+    #[test]
+    fn step_frame_ticks_the_timers_exactly_once_regardless_of_cycle_count() {
+        let mut chip8 = Chip8::new();
+        chip8.set_dt(10);
+
+        chip8.step_frame(0);
+
+        assert_eq!(chip8.get_dt(), 9);
+    }
+
+    #[test]
+    fn tick_timers_saturate_instead_of_wrapping_below_zero() {
+        let mut chip8 = Chip8::new();
+        chip8.set_dt(0);
+        chip8.set_st(0);
+
+        chip8.step_frame(0);
+
+        assert_eq!(chip8.get_dt(), 0);
+        assert_eq!(chip8.get_st(), 0);
+    }
+
+    #[test]
+    fn is_buzzing_reflects_whether_the_sound_timer_is_non_zero() {
+        let mut chip8 = Chip8::new();
+
+        chip8.set_st(5);
+        assert!(chip8.is_buzzing());
+
+        chip8.set_st(0);
+        assert!(!chip8.is_buzzing());
+    }
+
+    #[test]
+    fn step_frame_reports_started_when_the_buzzer_turns_on_this_frame() {
+        // LD V0, 5 ; LD ST, V0
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(&[0x60, 0x05, 0xF0, 0x18]).unwrap();
+
+        let transition = chip8.step_frame(2);
+
+        assert!(transition == BuzzerTransition::Started);
+    }
+
+    #[test]
+    fn step_frame_reports_stopped_when_the_sound_timer_reaches_zero() {
+        let mut chip8 = Chip8::new();
+        chip8.set_st(1);
+
+        let transition = chip8.step_frame(0);
+
+        assert!(transition == BuzzerTransition::Stopped);
+    }
+
+    #[test]
+    fn step_frame_reports_no_transition_while_the_buzzer_state_is_unchanged() {
+        let mut chip8 = Chip8::new();
+        chip8.set_st(5);
+
+        let transition = chip8.step_frame(0);
+
+        assert!(transition == BuzzerTransition::None);
+    }
+
+    #[test]
+    fn ret_returns_to_the_address_pushed_by_call() {
+        let mut chip8 = Chip8::new();
+        chip8.set_pc(0x200);
+
+        chip8.call(0x300);
+        assert_eq!(chip8.get_pc(), 0x300);
+
+        chip8.ret();
+        assert_eq!(chip8.get_pc(), 0x200);
+    }
+
+    #[test]
+    fn jumping_into_the_middle_of_a_cached_block_does_not_replay_folded_constants() {
+        // LD V0, 5 ; LD V1, 5 ; ADD V0, 1 ; JMP 0x206 (ends the block)
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(&[0x60, 0x05, 0x61, 0x05, 0x70, 0x01, 0x12, 0x06]).unwrap();
+
+        // Run the first instruction so the block gets decoded and cached,
+        // with `ADD V0, 1` folded into `SetV(0, 6)` under the assumption
+        // that V0 is still 5 when it runs.
+        chip8.execute_instruction();
+        assert_eq!(chip8.get_v(0), 5);
+
+        // Simulate a jump landing directly on the `ADD V0, 1` in the middle
+        // of that same block, with V0 having since become 10 through some
+        // other path. The cached SetV(0, 6) must not be replayed here.
+        chip8.set_v(0, 10);
+        chip8.set_pc(0x204);
+        chip8.execute_instruction();
+
+        assert_eq!(chip8.get_v(0), 11);
+    }
+
+    #[test]
+    fn self_modifying_write_invalidates_the_cached_block_covering_it() {
+        // LD V0, 5 ; JMP 0x206 (ends the block)
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(&[0x60, 0x05, 0x12, 0x06]).unwrap();
+
+        chip8.execute_instruction();
+        assert!(chip8.block_cache.contains_key(&0x200));
+
+        chip8.set_i(0x200);
+        chip8.ld_into_mem(0);
+
+        assert!(!chip8.block_cache.contains_key(&0x200));
+    }
 }
\ No newline at end of file
@@ -1,4 +1,5 @@
 mod chip8;
+mod recompiler;
 
 use chip8::Chip8;
 use wasm_bindgen::prelude::*;
@@ -0,0 +1,278 @@
+//! Caches decoded basic blocks of straight-line CHIP-8 code so
+//! `Chip8::execute_instruction` doesn't have to re-run the full
+//! `decode_instruction!` ladder on every single cycle.
+//!
+//! A block covers `[start, end)` in `memory`: a run of opcodes decoded once
+//! into `MicroOp`s, starting right after the previous control-flow opcode
+//! (or at the current PC) and ending right before the next one (`JMP`,
+//! `CALL`, `RET`, any skip, or `DRW`). `ops[i]` always corresponds to the
+//! opcode at `start + 2*i`, so a caller can still execute one instruction
+//! at a time by indexing into the block instead of replaying it whole.
+//!
+//! Only a handful of pure register opcodes are modeled as micro-ops;
+//! everything else is kept as `Raw` and re-dispatched through the normal
+//! opcode ladder when it runs, so behaviour never depends on whether an
+//! opcode made it into the IR. Since a `Raw` op's actual reads/writes are
+//! unknown to this module, both cleanup passes below treat it as an opaque
+//! barrier rather than guessing.
+
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MicroOp {
+    SetV(u8, u8),
+    MovV(u8, u8),
+    OrV(u8, u8),
+    AndV(u8, u8),
+    XorV(u8, u8),
+    AddVImm(u8, u8),
+    LoadI(u16),
+    Raw(u16),
+    /// An op whose result was proven dead; keeps its slot so `ops[i]` still
+    /// lines up with `start + 2*i`.
+    Dead,
+}
+
+impl MicroOp {
+    /// The register this op writes, if any.
+    fn dest(&self) -> Option<u8> {
+        return match *self {
+            MicroOp::SetV(x, _)
+            | MicroOp::MovV(x, _)
+            | MicroOp::OrV(x, _)
+            | MicroOp::AndV(x, _)
+            | MicroOp::XorV(x, _)
+            | MicroOp::AddVImm(x, _) => Some(x),
+            MicroOp::LoadI(_) | MicroOp::Raw(_) | MicroOp::Dead => None,
+        };
+    }
+
+    /// The registers this op reads (including its own destination, for the
+    /// read-modify-write ops).
+    fn reads(&self) -> [Option<u8>; 2] {
+        return match *self {
+            MicroOp::MovV(_, y) => [Some(y), None],
+            MicroOp::OrV(x, y) | MicroOp::AndV(x, y) | MicroOp::XorV(x, y) => [Some(x), Some(y)],
+            MicroOp::AddVImm(x, _) => [Some(x), None],
+            MicroOp::SetV(_, _) | MicroOp::LoadI(_) | MicroOp::Raw(_) | MicroOp::Dead => [None, None],
+        };
+    }
+}
+
+/// A cached run of straight-line code. Invalidated whenever a memory store
+/// lands inside `[start, end)`, e.g. self-modifying code via `LD [I], Vx`.
+pub struct Block {
+    pub start: u16,
+    pub end: u16,
+    pub ops: Vec<MicroOp>,
+}
+
+const MAX_BLOCK_LEN: usize = 512;
+
+/// Decodes a fresh block starting at `start`, applying two SkVM-style
+/// cleanup passes before it's cached:
+/// - dead code elimination: an op whose destination register is overwritten
+///   again before it's ever read is replaced with `MicroOp::Dead`, since
+///   nothing observes it.
+/// - constant folding: an op reading only block-invariant (already-constant)
+///   registers is reduced to the `SetV` that produces the same result.
+///
+/// The scan also stops at the end of `memory`, treating it like an implicit
+/// control-flow opcode, so a block can never index past `0xFFF`.
+pub fn decode_block(memory: &[u8; 0x1000], start: u16) -> Block {
+    let mut pc = start;
+    let mut ops = Vec::new();
+
+    loop {
+        if usize::from(pc) + 1 >= memory.len() {
+            return Block{ start, end: pc, ops: fold_constants(eliminate_dead_ops(ops)) };
+        }
+
+        let opcode = u16::from(memory[pc as usize]) << 8 | u16::from(memory[(pc + 1) as usize]);
+        let c = opcode >> 12 & 0xF;
+        let nn: u8 = (opcode & 0xFF) as u8;
+        let n: u8 = (opcode & 0xF) as u8;
+        let x: u8 = (opcode >> 8 & 0xF) as u8;
+        let y: u8 = (opcode >> 4 & 0xF) as u8;
+
+        let is_control_flow = (c == 0x0 && nn == 0xEE)
+            || c == 0x1
+            || c == 0x2
+            || c == 0x3
+            || c == 0x4
+            || (c == 0x5 && n == 0x0)
+            || (c == 0x9 && n == 0x0)
+            || c == 0xB
+            || c == 0xD
+            || (c == 0xE && (nn == 0x9E || nn == 0xA1));
+
+        if is_control_flow || ops.len() >= MAX_BLOCK_LEN {
+            return Block{ start, end: pc, ops: fold_constants(eliminate_dead_ops(ops)) };
+        }
+
+        let nnn: u16 = opcode & 0xFFF;
+        ops.push(match (c, n) {
+            (0x6, _) => MicroOp::SetV(x, nn),
+            (0x7, _) => MicroOp::AddVImm(x, nn),
+            (0x8, 0x0) => MicroOp::MovV(x, y),
+            (0x8, 0x1) => MicroOp::OrV(x, y),
+            (0x8, 0x2) => MicroOp::AndV(x, y),
+            (0x8, 0x3) => MicroOp::XorV(x, y),
+            (0xA, _) => MicroOp::LoadI(nnn),
+            _ => MicroOp::Raw(opcode),
+        });
+
+        pc = pc.wrapping_add(2);
+    }
+}
+
+fn eliminate_dead_ops(ops: Vec<MicroOp>) -> Vec<MicroOp> {
+    let len = ops.len();
+    let mut dead = vec![false; len];
+    let mut overwritten_before_read: HashMap<u8, bool> = HashMap::new();
+
+    for i in (0..len).rev() {
+        let op = ops[i];
+
+        // A Raw op's real reads/writes aren't tracked, so treat it as a
+        // barrier: nothing before it can be proven dead by a write after it.
+        if matches!(op, MicroOp::Raw(_)) {
+            overwritten_before_read.clear();
+            continue;
+        }
+
+        // Check and mark the write before the reads: a read-modify-write op
+        // (e.g. `AddVImm`) reads its destination register before writing it,
+        // so that read must win and mark the register live for whatever
+        // precedes this op, rather than the write's "not yet read" flag
+        // surviving past its own op's read of the same register.
+        if let Some(written) = op.dest() {
+            if *overwritten_before_read.get(&written).unwrap_or(&false) {
+                dead[i] = true;
+            }
+            overwritten_before_read.insert(written, true);
+        }
+        for read in op.reads().iter().flatten() {
+            overwritten_before_read.insert(*read, false);
+        }
+    }
+
+    return ops.into_iter().zip(dead)
+        .map(|(op, dead)| if dead { MicroOp::Dead } else { op })
+        .collect();
+}
+
+fn fold_constants(ops: Vec<MicroOp>) -> Vec<MicroOp> {
+    let mut known: HashMap<u8, u8> = HashMap::new();
+
+    return ops.into_iter().map(|op| {
+        // A Raw op might write any register with a value this module can't
+        // predict, so forget everything we thought we knew past this point.
+        if matches!(op, MicroOp::Raw(_)) {
+            known.clear();
+            return op;
+        }
+
+        let folded = match op {
+            MicroOp::SetV(x, imm) => Some((x, imm)),
+            MicroOp::MovV(x, y) => known.get(&y).map(|&v| (x, v)),
+            MicroOp::AddVImm(x, imm) => known.get(&x).map(|&v| (x, v.wrapping_add(imm))),
+            MicroOp::OrV(x, y) => known.get(&x).zip(known.get(&y)).map(|(&a, &b)| (x, a | b)),
+            MicroOp::AndV(x, y) => known.get(&x).zip(known.get(&y)).map(|(&a, &b)| (x, a & b)),
+            MicroOp::XorV(x, y) => known.get(&x).zip(known.get(&y)).map(|(&a, &b)| (x, a ^ b)),
+            MicroOp::LoadI(_) | MicroOp::Raw(_) | MicroOp::Dead => None,
+        };
+
+        if let Some((x, value)) = folded {
+            known.insert(x, value);
+            return MicroOp::SetV(x, value);
+        }
+
+        if let Some(written) = op.dest() {
+            known.remove(&written);
+        }
+        return op;
+    }).collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_with(start: u16, opcodes: &[u16]) -> [u8; 0x1000] {
+        let mut memory = [0; 0x1000];
+        for (i, opcode) in opcodes.iter().enumerate() {
+            let addr = usize::from(start) + i * 2;
+            memory[addr] = (opcode >> 8 & 0xFF) as u8;
+            memory[addr + 1] = (opcode & 0xFF) as u8;
+        }
+        return memory;
+    }
+
+    #[test]
+    fn decode_block_folds_constants_until_the_next_control_flow_opcode() {
+        // LD V0, 5 ; LD V1, 5 ; ADD V0, 1 ; JMP 0x206 (ends the block)
+        let memory = memory_with(0x200, &[0x6005, 0x6105, 0x7001, 0x1206]);
+
+        let block = decode_block(&memory, 0x200);
+
+        assert_eq!(block.end, 0x206);
+        assert_eq!(block.ops, vec![
+            MicroOp::SetV(0, 5),
+            MicroOp::SetV(1, 5),
+            MicroOp::SetV(0, 6),
+        ]);
+    }
+
+    #[test]
+    fn decode_block_eliminates_a_write_overwritten_before_its_read() {
+        // LD V0, 5 ; LD V0, 7 ; JMP 0x204 (ends the block)
+        let memory = memory_with(0x200, &[0x6005, 0x6007, 0x1204]);
+
+        let block = decode_block(&memory, 0x200);
+
+        assert_eq!(block.ops, vec![MicroOp::Dead, MicroOp::SetV(0, 7)]);
+    }
+
+    #[test]
+    fn decode_block_keeps_a_write_that_is_read_before_being_overwritten() {
+        // LD V0, 5 ; LD V1, V0 ; LD V0, 7 ; JMP 0x206 (ends the block)
+        let memory = memory_with(0x200, &[0x6005, 0x8100, 0x6007, 0x1206]);
+
+        let block = decode_block(&memory, 0x200);
+
+        assert_eq!(block.ops, vec![
+            MicroOp::SetV(0, 5),
+            MicroOp::SetV(1, 5),
+            MicroOp::SetV(0, 7),
+        ]);
+    }
+
+    #[test]
+    fn decode_block_treats_raw_ops_as_barriers_for_dce_and_folding() {
+        // LD V0, 5 ; SHR V0 (Raw, conservatively clears DCE/fold state) ;
+        // LD V0, 7 ; JMP 0x208 (ends the block)
+        let memory = memory_with(0x200, &[0x6005, 0x8006, 0x6007, 0x1208]);
+
+        let block = decode_block(&memory, 0x200);
+
+        assert_eq!(block.end, 0x206);
+        assert_eq!(block.ops, vec![
+            MicroOp::SetV(0, 5),
+            MicroOp::Raw(0x8006),
+            MicroOp::SetV(0, 7),
+        ]);
+    }
+
+    #[test]
+    fn decode_block_stops_at_the_end_of_memory_instead_of_indexing_past_it() {
+        // A straight line of raw opcodes with no control-flow opcode before
+        // the end of memory, starting one opcode short of the top.
+        let memory = memory_with(0xFFC, &[0x8006, 0x8006]);
+
+        let block = decode_block(&memory, 0xFFC);
+
+        assert_eq!(block.end, 0x1000);
+        assert_eq!(block.ops, vec![MicroOp::Raw(0x8006), MicroOp::Raw(0x8006)]);
+    }
+}